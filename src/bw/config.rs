@@ -7,22 +7,118 @@ use tun_tap::Mode;
 use untrusted::Input;
 
 use std::net::{SocketAddr, IpAddr, Ipv4Addr};
-use std::io::{Error, Read};
+use std::io::{Error, ErrorKind, Read};
 use std::fs::File;
 use base64;
 
+use ipnetwork::IpNetwork;
+
+use std::convert::TryFrom;
+
+use super::daemon::{self, ResolvedIds};
+use super::dns;
+use super::masked::{MaskedBytes, MaskedString};
+use super::mtu::Mtu;
+use super::peers::{PeerEndpoint, PeerTable};
+use super::router::ForwardingTable;
+
+/// How the raw text of a `FileOrValue` should be turned into key material.
+/// `Raw` (the default for `file`) passes the bytes through untouched, since
+/// a file's contents are usually already the binary key; `Base64` (the
+/// default for `value`) keeps inline values compact and human-pasteable.
+/// `Pem` accepts either a bare 32-byte Ed25519 seed or a standard PKCS8
+/// Ed25519 private key (e.g. `openssl genpkey -algorithm ed25519`), since
+/// tooling that exports PEM almost always produces the latter.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyEncoding {
+    Raw,
+    Base64,
+    Hex,
+    Pem,
+}
+
+/// The fixed 16-byte DER prefix of an RFC 8410 PKCS8 v1 Ed25519 private key
+/// with no public key or attributes present (version + AlgorithmIdentifier
+/// + the outer OCTET STRING wrapper around the 32-byte seed). This is
+/// exactly what `openssl genpkey -algorithm ed25519` emits, and matches the
+/// fixed template ring itself expects for `from_pkcs8`.
+const PKCS8_ED25519_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// Pull the raw 32-byte Ed25519 seed out of a decoded PEM block's DER
+/// contents, accepting either the bare seed or the standard PKCS8 wrapping.
+fn pkcs8_ed25519_seed(der: &[u8]) -> Result<Bytes, Error> {
+    if der.len() == 32 {
+        return Ok(Bytes::from(der.to_vec()));
+    }
+
+    if der.len() == 48 && der[..16] == PKCS8_ED25519_PREFIX {
+        return Ok(Bytes::from(der[16..].to_vec()));
+    }
+
+    Err(Error::new(
+        ErrorKind::InvalidData,
+        "PEM key material is not a bare 32-byte Ed25519 seed or a standard PKCS8 Ed25519 private key",
+    ))
+}
+
+impl KeyEncoding {
+    fn default_for_file() -> KeyEncoding { KeyEncoding::Raw }
+    fn default_for_value() -> KeyEncoding { KeyEncoding::Base64 }
+
+    fn decode(&self, data: &[u8]) -> Result<Bytes, Error> {
+        match self {
+            KeyEncoding::Raw => Ok(Bytes::from(data.to_vec())),
+
+            KeyEncoding::Base64 => {
+                let text = std::str::from_utf8(data)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("key material is not valid UTF-8: {}", e)))?;
+
+                base64::decode(text)
+                    .map(Bytes::from)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid base64 key material: {}", e)))
+            }
+
+            KeyEncoding::Hex => {
+                let text = std::str::from_utf8(data)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("key material is not valid UTF-8: {}", e)))?;
+
+                hex::decode(text.trim())
+                    .map(Bytes::from)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid hex key material: {}", e)))
+            }
+
+            KeyEncoding::Pem => {
+                let text = std::str::from_utf8(data)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("key material is not valid UTF-8: {}", e)))?;
+
+                let parsed = pem::parse(text)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid PEM key material: {}", e)))?;
+
+                pkcs8_ed25519_seed(&parsed.contents)
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum FileOrValue {
     File {
-        file: String,
+        file: MaskedString,
+        #[serde(default = "KeyEncoding::default_for_file")]
+        encoding: KeyEncoding,
         #[serde(default, skip)]
-        cache: Option<Bytes>,
+        cache: Option<MaskedBytes>,
     },
     Value {
-        value: String,
+        value: MaskedString,
+        #[serde(default = "KeyEncoding::default_for_value")]
+        encoding: KeyEncoding,
         #[serde(default, skip)]
-        cache: Option<Bytes>,
+        cache: Option<MaskedBytes>,
     },
 }
 
@@ -30,8 +126,9 @@ impl FileOrValue {
     #[allow(dead_code)]
     pub fn with_value(data: Bytes) -> FileOrValue {
         FileOrValue::File {
-            file: "/tmp/mem".to_string(),
-            cache: Some(data),
+            file: MaskedString::from("/tmp/mem"),
+            encoding: KeyEncoding::Raw,
+            cache: Some(MaskedBytes::from(data)),
         }
     }
 
@@ -39,12 +136,12 @@ impl FileOrValue {
         let val = self.get_value()?;
 
         match *self {
-            FileOrValue::File { file: _, ref mut cache } => {
-                *cache = Some(val);
+            FileOrValue::File { file: _, encoding: _, ref mut cache } => {
+                *cache = Some(MaskedBytes::from(val));
             }
 
-            FileOrValue::Value { value: _, ref mut cache } => {
-                *cache = Some(val);
+            FileOrValue::Value { value: _, encoding: _, ref mut cache } => {
+                *cache = Some(MaskedBytes::from(val));
             }
         }
 
@@ -53,24 +150,23 @@ impl FileOrValue {
 
     pub fn get_value(&self) -> Result<Bytes, Error> {
         match *self {
-            FileOrValue::Value { ref value, ref cache } => {
-                Ok(if let Some(ref cache) = cache {
-                    cache.clone()
-                } else {
-                    Bytes::from(base64::decode(value).unwrap())
-                })
+            FileOrValue::Value { ref value, ref encoding, ref cache } => {
+                if let Some(ref cache) = cache {
+                    return Ok(cache.get_value());
+                }
+
+                encoding.decode(value.get_value().as_bytes())
             }
 
-            FileOrValue::File { ref file, ref cache } => {
+            FileOrValue::File { ref file, ref encoding, ref cache } => {
                 if let Some(ref cache) = cache {
-                    return Ok(cache.clone());
+                    return Ok(cache.get_value());
                 }
 
-                let mut fd = File::open(file)?;
+                let mut fd = File::open(file.get_value())?;
                 let mut contents = Vec::new();
                 fd.read_to_end(&mut contents)?;
-                let contents = Bytes::from(contents);
-                Ok(contents)
+                encoding.decode(&contents)
             }
         }
     }
@@ -80,7 +176,7 @@ impl FileOrValue {
 pub struct Config {
     pub key: FileOrValue,
     #[serde(skip, default)]
-    public_key: Bytes,
+    public_key: MaskedBytes,
     #[serde(default, skip)]
     cached_network_id: Option<Bytes>,
     #[serde(rename = "network-id")]
@@ -94,26 +190,55 @@ pub struct Config {
     pub interface: InterfaceConfig,
     #[serde(default)]
     pub router: RouterConfig,
+    #[serde(default)]
+    pub transport: TransportConfig,
 }
 
 impl Config {
     pub fn get_public_key(&self) -> Bytes {
-        self.public_key.clone()
+        self.public_key.get_value()
     }
 
-    pub fn get_key_pair(&self) -> Ed25519KeyPair {
-        Ed25519KeyPair::from_seed_unchecked(Input::from(&self.key.get_value().unwrap())).unwrap()
+    pub fn get_key_pair(&self) -> Result<Ed25519KeyPair, Error> {
+        let seed = self.key.get_value()?;
+
+        Ed25519KeyPair::from_seed_unchecked(Input::from(&seed))
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "invalid Ed25519 key seed, expected 32 bytes"))
     }
 
     pub fn load(&mut self) -> Result<(), Error> {
-        self.public_key = Bytes::from(self.get_key_pair().public_key_bytes());
+        self.public_key = MaskedBytes::from(Bytes::from(self.get_key_pair()?.public_key_bytes()));
         self.cached_network_id = Some(self.get_network_id());
         self.auth.load()?;
         self.key.load()?;
+        self.server.load()?;
+        self.interface.load(&self.static_peers())?;
+        // Validated for its own sake: fail fast if `router.name` doesn't
+        // have the config section it needs, rather than discovering that
+        // once the datapath tries to build the table at runtime.
+        ForwardingTable::try_from(&self.router)?;
 
         Ok(())
     }
 
+    /// Build the live forwarding table the datapath consults for every
+    /// frame/packet, per `router.name`.
+    pub fn build_forwarding_table(&self) -> Result<ForwardingTable, Error> {
+        ForwardingTable::try_from(&self.router)
+    }
+
+    /// The peers known up front from statically configured `[[network]]`
+    /// entries, used to seed MTU discovery before DNS-based peers resolve.
+    fn static_peers(&self) -> Vec<SocketAddr> {
+        self.networks
+            .iter()
+            .flat_map(|network| match network {
+                NetworkConfig::PeersNetworkConfig(peers) => peers.peers.clone(),
+                NetworkConfig::DnsNetworkConfig(_) => Vec::new(),
+            })
+            .collect()
+    }
+
     pub fn get_network_id(&self) -> Bytes {
         if let Some(ref cache) = self.cached_network_id {
             cache.clone()
@@ -121,6 +246,23 @@ impl Config {
             Bytes::from(self.network_id.clone().into_bytes())
         }
     }
+
+    /// Build the live peer table for the datapath: seed it with every
+    /// statically configured peer, then spawn a background discovery thread
+    /// per `[[network]] type = "dns"` entry that keeps merging in whatever
+    /// it resolves.
+    pub fn build_peer_table(&self) -> PeerTable {
+        let table = PeerTable::new();
+        table.set_static(self.static_peers().into_iter().map(PeerEndpoint::from));
+
+        for network in &self.networks {
+            if let NetworkConfig::DnsNetworkConfig(dns_config) = network {
+                dns::spawn(dns_config.clone(), table.clone());
+            }
+        }
+
+        table
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -133,12 +275,62 @@ pub struct ServerConfig {
     pub ip: IpAddr,
     #[serde(default, rename = "unix-socket")]
     pub unix_socket: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub daemonize: bool,
+    #[serde(default, rename = "pid-file")]
+    pub pid_file: Option<String>,
+    #[serde(default)]
+    pub stdout: Option<String>,
+    #[serde(default)]
+    pub stderr: Option<String>,
+    #[serde(skip, default)]
+    resolved_ids: ResolvedIds,
 }
 
 impl ServerConfig {
     fn default_threads() -> u8 { 2 }
     fn default_port() -> u16 { 0 }
     fn default_ip() -> IpAddr { IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)) }
+
+    /// Resolve `user`/`group` to numeric ids, failing fast if either account
+    /// doesn't exist rather than discovering that after `setuid(2)` fails.
+    pub fn load(&mut self) -> Result<(), Error> {
+        self.resolved_ids = ResolvedIds {
+            uid: self.user.as_deref().map(daemon::resolve_user).transpose()?,
+            gid: self.group.as_deref().map(daemon::resolve_group).transpose()?,
+        };
+
+        Ok(())
+    }
+
+    pub fn resolved_ids(&self) -> ResolvedIds {
+        self.resolved_ids
+    }
+
+    /// The post-bind startup step: fork/detach if `daemonize` is set,
+    /// redirect std streams, write the PID file, then drop from root to the
+    /// resolved `user`/`group`. Call this once the privileged socket and
+    /// TUN/TAP interface are already open — dropping privileges any earlier
+    /// would stop the daemon from being able to create them.
+    pub fn start_daemon(&self) -> Result<(), Error> {
+        if self.daemonize {
+            daemon::daemonize()?;
+        }
+
+        daemon::redirect_stdio(self.stdout.as_deref(), self.stderr.as_deref())?;
+
+        if let Some(ref pid_file) = self.pid_file {
+            daemon::write_pid_file(pid_file)?;
+        }
+
+        daemon::drop_privileges(self.resolved_ids)?;
+
+        Ok(())
+    }
 }
 
 impl Default for ServerConfig {
@@ -148,6 +340,13 @@ impl Default for ServerConfig {
             unix_socket: None,
             ip: ServerConfig::default_ip(),
             port: ServerConfig::default_port(),
+            user: None,
+            group: None,
+            daemonize: false,
+            pid_file: None,
+            stdout: None,
+            stderr: None,
+            resolved_ids: ResolvedIds::default(),
         }
     }
 }
@@ -163,7 +362,13 @@ pub enum NetworkConfig {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DnsNetworkConfig {
-    pub domain: String
+    pub domain: String,
+    #[serde(default = "DnsNetworkConfig::default_refresh_interval", rename = "refresh-interval")]
+    pub refresh_interval: u64,
+}
+
+impl DnsNetworkConfig {
+    fn default_refresh_interval() -> u64 { 30 }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -179,7 +384,13 @@ pub struct InterfaceConfig {
     #[serde(default = "InterfaceConfig::default_name")]
     pub name: String,
     #[serde(default = "InterfaceConfig::default_mtu")]
-    pub mtu: u32,
+    pub mtu: Mtu,
+    #[serde(default)]
+    pub address: Vec<IpNetwork>,
+    #[serde(default)]
+    pub routes: Vec<IpNetwork>,
+    #[serde(skip, default)]
+    resolved_mtu: Option<u32>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Ord, PartialOrd, Eq, PartialEq)]
@@ -200,8 +411,25 @@ impl From<InterfaceConfigMode> for Mode {
 
 impl InterfaceConfig {
     fn default_name() -> String { "bw%d".to_string() }
-    fn default_mtu() -> u32 { 1400 }
+    fn default_mtu() -> Mtu { Mtu::default() }
     fn default_mode() -> InterfaceConfigMode { InterfaceConfigMode::Tap }
+
+    /// Resolve `mtu`, probing the path MTU to `peers` when it is `"auto"`,
+    /// and cache the result. Call once the peer set for the network is known.
+    pub fn load(&mut self, peers: &[SocketAddr]) -> Result<(), Error> {
+        self.resolved_mtu = Some(self.mtu.resolve(peers));
+
+        Ok(())
+    }
+
+    /// The concrete MTU the datapath should apply to the interface. Falls
+    /// back to resolving against an empty peer set if `load` hasn't run yet.
+    pub fn get_mtu(&self) -> u32 {
+        match self.resolved_mtu {
+            Some(mtu) => mtu,
+            None => self.mtu.resolve(&[]),
+        }
+    }
 }
 
 impl Default for InterfaceConfig {
@@ -210,6 +438,9 @@ impl Default for InterfaceConfig {
             mode: InterfaceConfig::default_mode(),
             name: InterfaceConfig::default_name(),
             mtu: InterfaceConfig::default_mtu(),
+            address: Vec::new(),
+            routes: Vec::new(),
+            resolved_mtu: None,
         }
     }
 }
@@ -250,22 +481,23 @@ impl CertificateAuthorityConfig {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SharedSecretConfig {
     #[serde(skip, default)]
-    cache: Option<Bytes>,
-    pub secret: String,
+    cache: Option<MaskedBytes>,
+    pub secret: MaskedString,
 }
 
 impl SharedSecretConfig {
     pub fn load(&mut self) -> Result<(), Error> {
-        self.cache = Some(self.get_secret());
+        let secret = self.get_secret();
+        self.cache = Some(MaskedBytes::from(secret));
 
         Ok(())
     }
 
     pub fn get_secret(&self) -> Bytes {
         if let Some(ref cache) = self.cache {
-            cache.clone()
+            cache.get_value()
         } else {
-            Bytes::from(self.secret.clone().into_bytes())
+            Bytes::from(self.secret.get_value().to_string().into_bytes())
         }
     }
 }
@@ -277,12 +509,18 @@ pub struct RouterConfig {
     #[cfg(feature = "python-router")]
     #[serde(default = "RouterConfig::default_python")]
     pub python: Option<PythonRouterConfig>,
+    #[serde(default)]
+    pub switch: Option<SwitchRouterConfig>,
+    #[serde(default, rename = "l3")]
+    pub l3_router: Option<L3RouterConfig>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum RouterChoice {
     Dumb,
+    Switch,
+    Router,
     #[cfg(feature = "python-router")]
     Python,
 }
@@ -299,6 +537,8 @@ impl Default for RouterConfig {
             name: RouterConfig::default_name(),
             #[cfg(feature = "python-router")]
             python: None,
+            switch: None,
+            l3_router: None,
         }
     }
 }
@@ -309,10 +549,177 @@ pub struct PythonRouterConfig {
     pub script: String,
 }
 
+/// Tuning for `router.name = "switch"`: a TAP-mode learning switch that
+/// forwards known unicast destinations directly and floods everything else.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SwitchRouterConfig {
+    #[serde(default = "SwitchRouterConfig::default_table_size", rename = "table-size")]
+    pub table_size: usize,
+    #[serde(default = "SwitchRouterConfig::default_learning_timeout", rename = "learning-timeout")]
+    pub learning_timeout: u64,
+}
+
+impl SwitchRouterConfig {
+    fn default_table_size() -> usize { 8192 }
+    fn default_learning_timeout() -> u64 { 300 }
+}
+
+impl Default for SwitchRouterConfig {
+    fn default() -> Self {
+        SwitchRouterConfig {
+            table_size: SwitchRouterConfig::default_table_size(),
+            learning_timeout: SwitchRouterConfig::default_learning_timeout(),
+        }
+    }
+}
+
+/// Tuning for `router.name = "router"`: a TUN-mode L3 router that forwards
+/// packets based on a configured IP-prefix-to-peer routing table.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct L3RouterConfig {
+    #[serde(default = "L3RouterConfig::default_table_size", rename = "table-size")]
+    pub table_size: usize,
+    #[serde(default)]
+    pub routes: Vec<L3Route>,
+}
+
+impl L3RouterConfig {
+    fn default_table_size() -> usize { 8192 }
+}
+
+impl Default for L3RouterConfig {
+    fn default() -> Self {
+        L3RouterConfig {
+            table_size: L3RouterConfig::default_table_size(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+/// A single static route: packets destined for `network` are forwarded to
+/// `peer`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct L3Route {
+    pub network: IpNetwork,
+    pub peer: SocketAddr,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TransportConfig {
+    #[serde(default = "TransportConfig::default_type", rename = "type")]
+    pub transport_type: TransportType,
+    #[serde(default)]
+    pub sni: Option<String>,
+    #[serde(default, rename = "host-header")]
+    pub host_header: Option<String>,
+    #[serde(default = "TransportConfig::default_keepalive_interval", rename = "keepalive-interval")]
+    pub keepalive_interval: u64,
+}
+
+impl TransportConfig {
+    fn default_type() -> TransportType { TransportType::Udp }
+    fn default_keepalive_interval() -> u64 { 30 }
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        TransportConfig {
+            transport_type: TransportConfig::default_type(),
+            sni: None,
+            host_header: None,
+            keepalive_interval: TransportConfig::default_keepalive_interval(),
+        }
+    }
+}
+
+/// The carrier the encrypted datapath frames are wrapped in. `Udp` is the
+/// historical raw-socket behaviour; the others trade a bit of overhead for
+/// being able to cross networks that block plain UDP/TCP.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportType {
+    Udp,
+    Tcp,
+    Tls,
+    #[serde(rename = "websocket")]
+    WebSocket,
+}
+
 #[cfg(test)]
 mod test {
+    use base64;
     use toml;
-    use super::Config;
+    use super::{Config, FileOrValue, KeyEncoding};
+
+    /// Wrap `contents` in a minimal PEM block without depending on the
+    /// `pem` crate's encoder, matching what `pem::parse` expects on the way
+    /// back in.
+    fn pem_block(tag: &str, contents: &[u8]) -> String {
+        let encoded = base64::encode(contents);
+        format!("-----BEGIN {tag}-----\n{encoded}\n-----END {tag}-----\n", tag = tag, encoded = encoded)
+    }
+
+    #[test]
+    fn test_key_encoding_base64_round_trips() {
+        let encoding = KeyEncoding::Base64;
+        assert_eq!(encoding.decode(b"aGVsbG8=").unwrap(), "hello".as_bytes());
+    }
+
+    #[test]
+    fn test_key_encoding_base64_bad_input_is_err_not_panic() {
+        let encoding = KeyEncoding::Base64;
+        assert!(encoding.decode(b"not-valid-base64!!!").is_err());
+    }
+
+    #[test]
+    fn test_key_encoding_hex_round_trips() {
+        let encoding = KeyEncoding::Hex;
+        assert_eq!(encoding.decode(b"68656c6c6f").unwrap(), "hello".as_bytes());
+    }
+
+    #[test]
+    fn test_key_encoding_hex_bad_input_is_err_not_panic() {
+        let encoding = KeyEncoding::Hex;
+        assert!(encoding.decode(b"not-hex").is_err());
+    }
+
+    #[test]
+    fn test_key_encoding_pem_bare_seed_round_trips() {
+        let seed = [0x42u8; 32];
+        let pem = pem_block("PRIVATE KEY", &seed);
+
+        let encoding = KeyEncoding::Pem;
+        assert_eq!(encoding.decode(pem.as_bytes()).unwrap(), &seed[..]);
+    }
+
+    #[test]
+    fn test_key_encoding_pem_pkcs8_wrapped_seed_round_trips() {
+        let seed = [0x07u8; 32];
+        let mut der = super::PKCS8_ED25519_PREFIX.to_vec();
+        der.extend_from_slice(&seed);
+        let pem = pem_block("PRIVATE KEY", &der);
+
+        let encoding = KeyEncoding::Pem;
+        assert_eq!(encoding.decode(pem.as_bytes()).unwrap(), &seed[..]);
+    }
+
+    #[test]
+    fn test_key_encoding_pem_bad_input_is_err_not_panic() {
+        let pem = pem_block("PRIVATE KEY", b"too-short-to-be-a-key");
+
+        let encoding = KeyEncoding::Pem;
+        assert!(encoding.decode(pem.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_file_or_value_value_decodes_with_configured_encoding() {
+        let mut value: FileOrValue = toml::from_str(r#"value = "68656c6c6f"
+encoding = "hex""#).unwrap();
+
+        assert_eq!(value.get_value().unwrap(), "hello".as_bytes());
+        value.load().unwrap();
+        assert_eq!(value.get_value().unwrap(), "hello".as_bytes());
+    }
 
     #[test]
     fn test_parsing() {
@@ -350,4 +757,78 @@ name = "dumb"
         assert_eq!(config.networks.len(), 2);
         assert_eq!(config.server.threads, 4);
     }
+
+    #[test]
+    fn test_debug_does_not_leak_secrets() {
+        let mut config: Config = toml::from_str(r#"
+key = { value = "MDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDA=" }
+network-id = "help"
+
+[[network]]
+type = "peers"
+peers = []
+
+[auth]
+secret = "very-secret-shared-secret"
+"#).unwrap();
+
+        // Check both before and after `load()`: `load()` populates the
+        // `cache` fields with the *decoded* secret bytes, which is exactly
+        // what leaked in the past even though the still-encoded `value`/
+        // `secret` fields were already masked.
+        let debug_before_load = format!("{:?}", config);
+        assert!(!debug_before_load.contains("MDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDA="));
+        assert!(!debug_before_load.contains("very-secret-shared-secret"));
+        assert!(debug_before_load.contains("***MASKED***"));
+
+        config.load().unwrap();
+
+        let debug_after_load = format!("{:?}", config);
+
+        assert!(!debug_after_load.contains("00000000000000000000000000000000"));
+        assert!(!debug_after_load.contains("very-secret-shared-secret"));
+        assert!(debug_after_load.contains("***MASKED***"));
+    }
+
+    #[test]
+    fn test_server_config_resolves_user_and_group() {
+        let mut server = super::ServerConfig {
+            user: Some("root".to_string()),
+            group: Some("root".to_string()),
+            ..Default::default()
+        };
+
+        server.load().unwrap();
+
+        assert_eq!(server.resolved_ids().uid, Some(0));
+        assert_eq!(server.resolved_ids().gid, Some(0));
+    }
+
+    #[test]
+    fn test_server_config_load_fails_for_unknown_user() {
+        let mut server = super::ServerConfig {
+            user: Some("no-such-user-blackwidow-test".to_string()),
+            ..Default::default()
+        };
+
+        assert!(server.load().is_err());
+    }
+
+    #[test]
+    fn test_start_daemon_writes_pid_file_without_daemonizing() {
+        let pid_file = std::env::temp_dir().join("blackwidow-test.pid");
+
+        let server = super::ServerConfig {
+            daemonize: false,
+            pid_file: Some(pid_file.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+
+        server.start_daemon().unwrap();
+
+        let written = std::fs::read_to_string(&pid_file).unwrap();
+        assert_eq!(written.trim(), std::process::id().to_string());
+
+        std::fs::remove_file(&pid_file).unwrap();
+    }
 }
\ No newline at end of file
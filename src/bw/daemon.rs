@@ -0,0 +1,167 @@
+use std::ffi::CString;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::os::unix::io::AsRawFd;
+
+/// Numeric uid/gid resolved from `ServerConfig::user`/`group` at load time,
+/// so a missing account is reported once at startup rather than failing
+/// deep inside `setuid(2)` after the socket is already bound.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResolvedIds {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+/// Resolve a user name to a uid via `getpwnam`.
+pub fn resolve_user(name: &str) -> Result<u32, Error> {
+    let cname = CString::new(name)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("invalid user name '{}'", name)))?;
+
+    let passwd = unsafe { libc::getpwnam(cname.as_ptr()) };
+
+    if passwd.is_null() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no such user '{}'", name),
+        ));
+    }
+
+    Ok(unsafe { (*passwd).pw_uid })
+}
+
+/// Resolve a group name to a gid via `getgrnam`.
+pub fn resolve_group(name: &str) -> Result<u32, Error> {
+    let cname = CString::new(name)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("invalid group name '{}'", name)))?;
+
+    let group = unsafe { libc::getgrnam(cname.as_ptr()) };
+
+    if group.is_null() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no such group '{}'", name),
+        ));
+    }
+
+    Ok(unsafe { (*group).gr_gid })
+}
+
+/// Fork and detach from the controlling terminal, the classic double-fork
+/// daemonize dance. Must run before any threads are spawned (sockets/the
+/// TUN/TAP fd survive `fork`, threads don't).
+pub fn daemonize() -> Result<(), Error> {
+    unsafe {
+        if libc::fork() > 0 {
+            libc::_exit(0);
+        }
+
+        if libc::setsid() < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        if libc::fork() > 0 {
+            libc::_exit(0);
+        }
+    }
+
+    Ok(())
+}
+
+/// Redirect stdout/stderr to the configured files, creating them if needed
+/// and appending to any existing contents. Called after `daemonize` so the daemon's output still lands
+/// somewhere once the terminal is gone.
+pub fn redirect_stdio(stdout: Option<&str>, stderr: Option<&str>) -> Result<(), Error> {
+    if let Some(path) = stdout {
+        redirect_fd(path, libc::STDOUT_FILENO)?;
+    }
+
+    if let Some(path) = stderr {
+        redirect_fd(path, libc::STDERR_FILENO)?;
+    }
+
+    Ok(())
+}
+
+fn redirect_fd(path: &str, fd: libc::c_int) -> Result<(), Error> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    let ret = unsafe { libc::dup2(file.as_raw_fd(), fd) };
+
+    if ret < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Write the current process id to `path`, overwriting any existing file.
+pub fn write_pid_file(path: &str) -> Result<(), Error> {
+    let pid = unsafe { libc::getpid() };
+    let mut file = fs::File::create(path)?;
+    writeln!(file, "{}", pid)
+}
+
+/// Drop from root to the resolved uid/gid. Supplementary groups are cleared
+/// first: a process forked as root otherwise keeps root's supplementary
+/// groups (e.g. `docker`, `disk`) after `setgid`/`setuid`, which would leave
+/// the daemon with group-based access it was meant to lose. Then group
+/// before user: once the uid changes, the process no longer has permission
+/// to call `setgid`.
+pub fn drop_privileges(ids: ResolvedIds) -> Result<(), Error> {
+    if ids.gid.is_some() || ids.uid.is_some() {
+        if unsafe { libc::setgroups(0, std::ptr::null()) } != 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+
+    if let Some(gid) = ids.gid {
+        if unsafe { libc::setgid(gid) } != 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+
+    if let Some(uid) = ids.uid {
+        if unsafe { libc::setuid(uid) } != 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{resolve_group, resolve_user, write_pid_file};
+
+    #[test]
+    fn test_resolve_user_known() {
+        assert_eq!(resolve_user("root").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_resolve_user_unknown() {
+        assert!(resolve_user("no-such-user-blackwidow-test").is_err());
+    }
+
+    #[test]
+    fn test_resolve_group_known() {
+        assert_eq!(resolve_group("root").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_resolve_group_unknown() {
+        assert!(resolve_group("no-such-group-blackwidow-test").is_err());
+    }
+
+    #[test]
+    fn test_write_pid_file_contains_current_pid() {
+        let path = std::env::temp_dir().join("blackwidow-daemon-test.pid");
+
+        write_pid_file(path.to_str().unwrap()).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.trim(), std::process::id().to_string());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use std::thread;
+use std::time::Duration;
+
+use bytes::Bytes;
+use trust_dns_resolver::Resolver;
+
+use super::config::DnsNetworkConfig;
+use super::peers::{PeerEndpoint, PeerTable};
+
+/// SRV service name black-widow peers advertise themselves under, e.g.
+/// `_blackwidow._udp.example.com`.
+const SRV_SERVICE: &str = "_blackwidow._udp";
+
+/// A discovered peer survives this many missed/failed refresh rounds
+/// before `PeerTable::merge` evicts it, so one transient resolution hiccup
+/// doesn't immediately drop a peer that's still there.
+const MISSED_ROUNDS_BEFORE_EVICTION: u64 = 3;
+
+/// Start a background thread that periodically resolves `config`'s domain
+/// and merges the result into `peers`. Runs until the process exits; there
+/// is one of these per `[[network]] type = "dns"` entry.
+pub fn spawn(config: DnsNetworkConfig, peers: PeerTable) {
+    let ttl = Duration::from_secs(config.refresh_interval.saturating_mul(MISSED_ROUNDS_BEFORE_EVICTION));
+
+    thread::spawn(move || loop {
+        match discover(&config) {
+            Ok(discovered) => peers.merge(discovered, ttl),
+            Err(err) => log::warn!("DNS discovery for '{}' failed: {}", config.domain, err),
+        }
+
+        thread::sleep(Duration::from_secs(config.refresh_interval));
+    });
+}
+
+/// Resolve one round of SRV + TXT records for `config.domain` into peer
+/// endpoints, ordered by SRV priority (ascending) then weight (descending).
+fn discover(config: &DnsNetworkConfig) -> Result<Vec<PeerEndpoint>, trust_dns_resolver::error::ResolveError> {
+    let resolver = Resolver::from_system_conf()?;
+
+    let srv_name = format!("{}.{}", SRV_SERVICE, config.domain);
+    let mut records: Vec<_> = resolver.srv_lookup(srv_name.as_str())?.into_iter().collect();
+    sort_srv_records(&mut records);
+
+    let mut endpoints = Vec::with_capacity(records.len());
+
+    for record in records {
+        let host = record.target().to_utf8();
+        let port = record.port();
+
+        let ips = match resolver.lookup_ip(host.as_str()) {
+            Ok(ips) => ips,
+            Err(err) => {
+                log::warn!("DNS discovery for '{}' could not resolve SRV target '{}': {}", config.domain, host, err);
+                continue;
+            }
+        };
+
+        let public_key = resolver
+            .txt_lookup(host.as_str())
+            .ok()
+            .and_then(|txt| txt.into_iter().next())
+            .and_then(|txt| txt.txt_data().first().cloned())
+            .map(|data| Bytes::from(data.into_vec()));
+
+        for ip in ips.iter() {
+            endpoints.push(PeerEndpoint {
+                addr: (ip, port).into(),
+                public_key: public_key.clone(),
+            });
+        }
+    }
+
+    Ok(endpoints)
+}
+
+/// Order SRV records the way a resolver is expected to use them: lowest
+/// priority first, ties broken by highest weight first.
+fn sort_srv_records(records: &mut [trust_dns_resolver::proto::rr::rdata::SRV]) {
+    records.sort_by(|a, b| a.priority().cmp(&b.priority()).then(b.weight().cmp(&a.weight())));
+}
+
+#[cfg(test)]
+mod test {
+    use trust_dns_resolver::proto::rr::{rdata::SRV, Name};
+
+    use super::sort_srv_records;
+
+    fn srv(priority: u16, weight: u16) -> SRV {
+        SRV::new(priority, weight, 4433, Name::from_ascii("peer.example.com.").unwrap())
+    }
+
+    #[test]
+    fn test_sorts_by_priority_ascending() {
+        let mut records = vec![srv(20, 0), srv(10, 0), srv(30, 0)];
+        sort_srv_records(&mut records);
+
+        let priorities: Vec<_> = records.iter().map(|r| r.priority()).collect();
+        assert_eq!(priorities, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_breaks_priority_ties_by_weight_descending() {
+        let mut records = vec![srv(10, 1), srv(10, 100), srv(10, 50)];
+        sort_srv_records(&mut records);
+
+        let weights: Vec<_> = records.iter().map(|r| r.weight()).collect();
+        assert_eq!(weights, vec![100, 50, 1]);
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::ops::Deref;
+
+use bytes::Bytes;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const MASK: &str = "***MASKED***";
+
+/// A `String` wrapper whose `Debug` impl never prints the wrapped value.
+///
+/// Used for config fields that hold secret material (keys, shared secrets, ...)
+/// so that `debug!("{:?}", config)` and panic dumps can't leak them. Serializes
+/// and deserializes exactly like a plain `String`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct MaskedString(String);
+
+impl MaskedString {
+    pub fn get_value(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for MaskedString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for MaskedString {
+    fn from(value: String) -> Self {
+        MaskedString(value)
+    }
+}
+
+impl From<&str> for MaskedString {
+    fn from(value: &str) -> Self {
+        MaskedString(value.to_string())
+    }
+}
+
+impl fmt::Debug for MaskedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+impl Serialize for MaskedString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for MaskedString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(MaskedString)
+    }
+}
+
+/// A `Bytes` wrapper whose `Debug` impl never prints the wrapped value.
+///
+/// Used for derived secret material (e.g. a loaded key's raw bytes) so it
+/// never ends up in logs, while `Deref`/`get_value` keep giving real access
+/// to callers that need it.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct MaskedBytes(Bytes);
+
+impl MaskedBytes {
+    pub fn get_value(&self) -> Bytes {
+        self.0.clone()
+    }
+}
+
+impl Deref for MaskedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Bytes> for MaskedBytes {
+    fn from(value: Bytes) -> Self {
+        MaskedBytes(value)
+    }
+}
+
+impl fmt::Debug for MaskedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+impl Serialize for MaskedBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Vec::from(&self.0[..]).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MaskedBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        Ok(MaskedBytes(Bytes::from(bytes)))
+    }
+}
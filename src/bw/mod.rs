@@ -0,0 +1,9 @@
+pub mod config;
+pub mod daemon;
+pub mod dns;
+pub mod keygen;
+pub mod masked;
+pub mod mtu;
+pub mod peers;
+pub mod router;
+pub mod transport;
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::net::{SocketAddr, UdpSocket};
+
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Per-packet overhead added by the black-widow datapath (framing + AEAD tag
+/// + the signature/auth header), subtracted from a discovered path MTU so
+/// the resulting interface MTU never produces a packet that fragments.
+const DATAPATH_OVERHEAD: u32 = 64;
+
+/// Fallback used when `mtu = "auto"` can't discover a path MTU for any peer.
+const FALLBACK_MTU: u32 = 1400;
+
+/// The smallest MTU we'll ever hand back from discovery. IPv6's minimum link
+/// MTU is 1280 and IPv4's is 576; below that a path is either misreported or
+/// too degraded to be worth trusting, so fall back instead of wedging the
+/// interface with an unusably small MTU.
+const MIN_USABLE_MTU: u32 = 576;
+
+/// The interface MTU: either a fixed value or `"auto"`, in which case it is
+/// derived from the path MTU to the configured peers at load time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mtu {
+    Auto,
+    Value(u32),
+}
+
+impl Mtu {
+    /// Resolve to a concrete MTU, probing `peers` when set to `Auto`.
+    pub fn resolve(&self, peers: &[SocketAddr]) -> u32 {
+        match *self {
+            Mtu::Value(v) => v,
+            Mtu::Auto => Mtu::discover(peers).unwrap_or(FALLBACK_MTU),
+        }
+    }
+
+    fn discover(peers: &[SocketAddr]) -> Option<u32> {
+        let mtu = peers
+            .iter()
+            .filter_map(|peer| path_mtu(*peer))
+            .min()?
+            .saturating_sub(DATAPATH_OVERHEAD);
+
+        if mtu < MIN_USABLE_MTU {
+            None
+        } else {
+            Some(mtu)
+        }
+    }
+}
+
+impl Default for Mtu {
+    fn default() -> Self {
+        Mtu::Value(FALLBACK_MTU)
+    }
+}
+
+/// Query the kernel's current path MTU estimate to `peer` by connecting a UDP
+/// socket and reading back `IP_MTU`/`IPV6_MTU`. Returns `None` when the
+/// platform doesn't expose this (or the socket can't be set up) so callers
+/// fall back to a sane default.
+#[cfg(target_os = "linux")]
+fn path_mtu(peer: SocketAddr) -> Option<u32> {
+    use std::os::unix::io::AsRawFd;
+
+    let bind_addr: SocketAddr = if peer.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+
+    let socket = UdpSocket::bind(bind_addr).ok()?;
+    socket.connect(peer).ok()?;
+
+    let level = if peer.is_ipv4() { libc::IPPROTO_IP } else { libc::IPPROTO_IPV6 };
+    let optname = if peer.is_ipv4() { libc::IP_MTU } else { libc::IPV6_MTU };
+
+    let mut mtu: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            level,
+            optname,
+            &mut mtu as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret == 0 && mtu > 0 {
+        Some(mtu as u32)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn path_mtu(_peer: SocketAddr) -> Option<u32> {
+    None
+}
+
+impl fmt::Display for Mtu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mtu::Auto => f.write_str("auto"),
+            Mtu::Value(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl Serialize for Mtu {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Mtu::Auto => serializer.serialize_str("auto"),
+            Mtu::Value(v) => serializer.serialize_u32(v),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Mtu {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum MtuRepr {
+            Str(String),
+            Value(u32),
+        }
+
+        match MtuRepr::deserialize(deserializer)? {
+            MtuRepr::Value(v) => Ok(Mtu::Value(v)),
+            MtuRepr::Str(ref s) if s.eq_ignore_ascii_case("auto") => Ok(Mtu::Auto),
+            MtuRepr::Str(s) => Err(de::Error::custom(format!("invalid mtu: \"{}\", expected a number or \"auto\"", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use toml;
+    use serde::{Deserialize, Serialize};
+
+    use super::{Mtu, FALLBACK_MTU};
+
+    #[derive(Deserialize, Serialize)]
+    struct Wrapper {
+        mtu: Mtu,
+    }
+
+    fn parse(toml_value: &str) -> Result<Mtu, toml::de::Error> {
+        toml::from_str::<Wrapper>(&format!("mtu = {}", toml_value)).map(|w| w.mtu)
+    }
+
+    #[test]
+    fn test_value_resolves_to_itself_without_probing() {
+        assert_eq!(Mtu::Value(1234).resolve(&[]), 1234);
+    }
+
+    #[test]
+    fn test_auto_falls_back_with_no_peers() {
+        assert_eq!(Mtu::Auto.resolve(&[]), FALLBACK_MTU);
+    }
+
+    #[test]
+    fn test_deserialize_auto_case_insensitive() {
+        assert_eq!(parse("\"auto\"").unwrap(), Mtu::Auto);
+        assert_eq!(parse("\"AUTO\"").unwrap(), Mtu::Auto);
+        assert_eq!(parse("\"Auto\"").unwrap(), Mtu::Auto);
+    }
+
+    #[test]
+    fn test_deserialize_numeric_value() {
+        assert_eq!(parse("1400").unwrap(), Mtu::Value(1400));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_other_strings() {
+        assert!(parse("\"jumbo\"").is_err());
+    }
+
+    #[test]
+    fn test_serialize_round_trips() {
+        assert_eq!(toml::to_string(&Wrapper { mtu: Mtu::Auto }).unwrap(), "mtu = \"auto\"\n");
+        assert_eq!(toml::to_string(&Wrapper { mtu: Mtu::Value(1400) }).unwrap(), "mtu = 1400\n");
+    }
+}
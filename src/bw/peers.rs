@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+/// A single reachable peer, whether it came from a static `[[network]]`
+/// entry or was learned through DNS discovery.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerEndpoint {
+    pub addr: SocketAddr,
+    pub public_key: Option<Bytes>,
+}
+
+impl From<SocketAddr> for PeerEndpoint {
+    fn from(addr: SocketAddr) -> Self {
+        PeerEndpoint { addr, public_key: None }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<SocketAddr, PeerEndpoint>,
+    /// Addresses that came from `set_static`. Tracked separately so a
+    /// discovery round that happens to also return a static peer's address
+    /// doesn't start the eviction clock on it.
+    static_addrs: HashSet<SocketAddr>,
+    /// Last time a `merge`d (non-static) peer was seen.
+    discovered_last_seen: HashMap<SocketAddr, Instant>,
+}
+
+/// The live set of peers the datapath sends/receives to, shared between the
+/// config-driven static peer list and any running discovery subsystems (DNS
+/// SRV/TXT, ...). Keyed by address so a rediscovered peer just refreshes its
+/// entry instead of duplicating it.
+#[derive(Clone, Default)]
+pub struct PeerTable {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl PeerTable {
+    pub fn new() -> Self {
+        PeerTable::default()
+    }
+
+    /// Insert the statically configured peers. These are never evicted by a
+    /// discovery refresh, only ever added to or overwritten by a discovered
+    /// peer at the same address.
+    pub fn set_static(&self, peers: impl IntoIterator<Item = PeerEndpoint>) {
+        let mut inner = self.inner.lock().unwrap();
+
+        for peer in peers {
+            inner.discovered_last_seen.remove(&peer.addr);
+            inner.static_addrs.insert(peer.addr);
+            inner.entries.insert(peer.addr, peer);
+        }
+    }
+
+    /// Merge one round of discovery results in: refreshes/adds each given
+    /// peer, then evicts any previously discovered peer that hasn't
+    /// reappeared within `ttl` - e.g. one that's been decommissioned and
+    /// removed from DNS. Statically configured peers are never evicted this
+    /// way, even if a discovery round also returns them.
+    pub fn merge(&self, peers: impl IntoIterator<Item = PeerEndpoint>, ttl: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        for peer in peers {
+            if !inner.static_addrs.contains(&peer.addr) {
+                inner.discovered_last_seen.insert(peer.addr, now);
+            }
+
+            inner.entries.insert(peer.addr, peer);
+        }
+
+        let expired: Vec<SocketAddr> = inner
+            .discovered_last_seen
+            .iter()
+            .filter(|(_, seen)| seen.elapsed() >= ttl)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        for addr in expired {
+            inner.discovered_last_seen.remove(&addr);
+            inner.entries.remove(&addr);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<PeerEndpoint> {
+        self.inner.lock().unwrap().entries.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::{PeerEndpoint, PeerTable};
+
+    fn endpoint(addr: &str, public_key: Option<&str>) -> PeerEndpoint {
+        PeerEndpoint {
+            addr: addr.parse().unwrap(),
+            public_key: public_key.map(|key| Bytes::from(key.as_bytes().to_vec())),
+        }
+    }
+
+    #[test]
+    fn test_merge_overwrites_static_peer_at_same_address() {
+        let table = PeerTable::new();
+        table.set_static(vec![endpoint("1.2.3.4:1234", None)]);
+        table.merge(vec![endpoint("1.2.3.4:1234", Some("discovered-key"))], Duration::from_secs(60));
+
+        let snapshot = table.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].public_key, Some(Bytes::from(b"discovered-key".to_vec())));
+    }
+
+    #[test]
+    fn test_merge_adds_a_genuinely_new_address() {
+        let table = PeerTable::new();
+        table.set_static(vec![endpoint("1.2.3.4:1234", None)]);
+        table.merge(vec![endpoint("5.6.7.8:5678", Some("discovered-key"))], Duration::from_secs(60));
+
+        let mut addrs: Vec<_> = table.snapshot().into_iter().map(|peer| peer.addr.to_string()).collect();
+        addrs.sort();
+
+        assert_eq!(addrs, vec!["1.2.3.4:1234".to_string(), "5.6.7.8:5678".to_string()]);
+    }
+
+    #[test]
+    fn test_merge_evicts_a_discovered_peer_that_stops_reappearing() {
+        let table = PeerTable::new();
+        table.merge(vec![endpoint("5.6.7.8:5678", None)], Duration::from_millis(10));
+
+        thread::sleep(Duration::from_millis(20));
+
+        // A later round that doesn't mention 5.6.7.8:5678 at all should
+        // drop it once its ttl has elapsed.
+        table.merge(vec![endpoint("9.9.9.9:9999", None)], Duration::from_millis(10));
+
+        let addrs: Vec<_> = table.snapshot().into_iter().map(|peer| peer.addr.to_string()).collect();
+        assert_eq!(addrs, vec!["9.9.9.9:9999".to_string()]);
+    }
+
+    #[test]
+    fn test_merge_never_evicts_a_static_peer() {
+        let table = PeerTable::new();
+        table.set_static(vec![endpoint("1.2.3.4:1234", None)]);
+        table.merge(vec![endpoint("1.2.3.4:1234", None)], Duration::from_millis(10));
+
+        thread::sleep(Duration::from_millis(20));
+
+        // Even though 1.2.3.4:1234 also came back through discovery once,
+        // it's static and must survive a round that omits it.
+        table.merge(Vec::new(), Duration::from_millis(10));
+
+        let addrs: Vec<_> = table.snapshot().into_iter().map(|peer| peer.addr.to_string()).collect();
+        assert_eq!(addrs, vec!["1.2.3.4:1234".to_string()]);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use ipnetwork::IpNetwork;
+
+use super::config::{L3RouterConfig, RouterChoice, RouterConfig, SwitchRouterConfig};
+
+/// An Ethernet MAC address as it appears in a TAP frame's source/destination
+/// field.
+pub type MacAddr = [u8; 6];
+
+const BROADCAST: MacAddr = [0xff; 6];
+
+/// A learning switch forwarding table for TAP mode: maps source MACs seen on
+/// incoming frames to the peer they arrived from, so later frames addressed
+/// to that MAC can be unicast instead of flooded. Mirrors a real Ethernet
+/// switch's MAC table, including per-entry aging.
+#[derive(Debug)]
+pub struct SwitchTable {
+    entries: HashMap<MacAddr, (SocketAddr, Instant)>,
+    max_entries: usize,
+    entry_timeout: Duration,
+}
+
+impl SwitchTable {
+    pub fn new(max_entries: usize, entry_timeout: Duration) -> Self {
+        SwitchTable {
+            entries: HashMap::new(),
+            max_entries,
+            entry_timeout,
+        }
+    }
+
+    /// Record that `mac` was last seen arriving from `peer`. Before the
+    /// table is treated as full, expired entries are purged so aging
+    /// actually reclaims space instead of the table filling up with stale
+    /// MACs and refusing to learn anything new forever.
+    pub fn learn(&mut self, mac: MacAddr, peer: SocketAddr) {
+        if mac == BROADCAST {
+            return;
+        }
+
+        if !self.entries.contains_key(&mac) {
+            self.purge_expired();
+
+            if self.entries.len() >= self.max_entries {
+                return;
+            }
+        }
+
+        self.entries.insert(mac, (peer, Instant::now()));
+    }
+
+    fn purge_expired(&mut self) {
+        let timeout = self.entry_timeout;
+        self.entries.retain(|_, (_, seen)| seen.elapsed() < timeout);
+    }
+
+    /// The peer a frame addressed to `mac` should be unicast to, or `None`
+    /// if it's unknown/broadcast/expired and should be flooded instead.
+    pub fn lookup(&self, mac: &MacAddr) -> Option<SocketAddr> {
+        if *mac == BROADCAST {
+            return None;
+        }
+
+        self.entries.get(mac).and_then(|(peer, seen)| {
+            if seen.elapsed() < self.entry_timeout {
+                Some(*peer)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// A static IP-prefix to peer routing table for TUN mode: forwards each
+/// packet to the peer whose configured route is the longest matching prefix
+/// for the packet's destination address.
+#[derive(Debug)]
+pub struct L3Table {
+    routes: Vec<(IpNetwork, SocketAddr)>,
+}
+
+impl L3Table {
+    pub fn new(mut routes: Vec<(IpNetwork, SocketAddr)>) -> Self {
+        routes.sort_by_key(|(network, _)| std::cmp::Reverse(network.prefix()));
+        L3Table { routes }
+    }
+
+    pub fn lookup(&self, dest: IpAddr) -> Option<SocketAddr> {
+        self.routes
+            .iter()
+            .find(|(network, _)| network.contains(dest))
+            .map(|(_, peer)| *peer)
+    }
+}
+
+impl From<&SwitchRouterConfig> for SwitchTable {
+    fn from(config: &SwitchRouterConfig) -> Self {
+        SwitchTable::new(config.table_size, Duration::from_secs(config.learning_timeout))
+    }
+}
+
+impl From<&L3RouterConfig> for L3Table {
+    fn from(config: &L3RouterConfig) -> Self {
+        let mut routes: Vec<_> = config.routes.iter().map(|route| (route.network, route.peer)).collect();
+
+        if routes.len() > config.table_size {
+            log::warn!(
+                "L3 router config has {} static routes, exceeding table-size {}; dropping the excess",
+                routes.len(),
+                config.table_size
+            );
+            routes.truncate(config.table_size);
+        }
+
+        L3Table::new(routes)
+    }
+}
+
+/// The actual forwarding table a `RouterConfig` resolves to, built once at
+/// startup and consulted by the datapath for every frame/packet.
+#[derive(Debug)]
+pub enum ForwardingTable {
+    /// `router.name = "dumb"`: no learning, the datapath floods everything.
+    Dumb,
+    Switch(SwitchTable),
+    L3(L3Table),
+}
+
+impl TryFrom<&RouterConfig> for ForwardingTable {
+    type Error = Error;
+
+    /// Build the table `router.name` selects, failing fast rather than
+    /// silently defaulting to `Dumb` if the matching `[router.switch]` /
+    /// `[router.l3]` section is missing.
+    fn try_from(config: &RouterConfig) -> Result<Self, Error> {
+        match config.name {
+            RouterChoice::Dumb => Ok(ForwardingTable::Dumb),
+
+            RouterChoice::Switch => {
+                let switch = config.switch.as_ref().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "router.name = \"switch\" requires a [router.switch] section")
+                })?;
+
+                Ok(ForwardingTable::Switch(SwitchTable::from(switch)))
+            }
+
+            RouterChoice::Router => {
+                let l3 = config.l3_router.as_ref().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "router.name = \"router\" requires a [router.l3] section")
+                })?;
+
+                Ok(ForwardingTable::L3(L3Table::from(l3)))
+            }
+
+            #[cfg(feature = "python-router")]
+            RouterChoice::Python => Ok(ForwardingTable::Dumb),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use std::net::SocketAddr;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::super::config::{RouterChoice, RouterConfig, SwitchRouterConfig};
+    use super::{ForwardingTable, SwitchTable, L3Table, MacAddr, BROADCAST};
+
+    fn peer(port: u16) -> SocketAddr {
+        format!("127.0.0.1:{}", port).parse().unwrap()
+    }
+
+    fn mac(last_byte: u8) -> MacAddr {
+        [0x02, 0x00, 0x00, 0x00, 0x00, last_byte]
+    }
+
+    #[test]
+    fn test_learns_and_looks_up_unicast_target() {
+        let mut table = SwitchTable::new(8, Duration::from_secs(60));
+        table.learn(mac(1), peer(1001));
+
+        assert_eq!(table.lookup(&mac(1)), Some(peer(1001)));
+    }
+
+    #[test]
+    fn test_unknown_mac_is_not_found() {
+        let table = SwitchTable::new(8, Duration::from_secs(60));
+        assert_eq!(table.lookup(&mac(1)), None);
+    }
+
+    #[test]
+    fn test_broadcast_is_never_learned_or_looked_up() {
+        let mut table = SwitchTable::new(8, Duration::from_secs(60));
+        table.learn(BROADCAST, peer(1001));
+
+        assert_eq!(table.lookup(&BROADCAST), None);
+    }
+
+    #[test]
+    fn test_entry_expires_after_timeout() {
+        let mut table = SwitchTable::new(8, Duration::from_millis(10));
+        table.learn(mac(1), peer(1001));
+
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(table.lookup(&mac(1)), None);
+    }
+
+    #[test]
+    fn test_expired_entries_are_purged_to_free_capacity() {
+        let mut table = SwitchTable::new(1, Duration::from_millis(10));
+        table.learn(mac(1), peer(1001));
+
+        thread::sleep(Duration::from_millis(20));
+
+        // The table is "full" by count, but the one entry in it is stale;
+        // learning a new MAC must reclaim that space instead of refusing.
+        table.learn(mac(2), peer(1002));
+
+        assert_eq!(table.lookup(&mac(2)), Some(peer(1002)));
+    }
+
+    #[test]
+    fn test_table_stays_full_of_live_entries() {
+        let mut table = SwitchTable::new(1, Duration::from_secs(60));
+        table.learn(mac(1), peer(1001));
+        table.learn(mac(2), peer(1002));
+
+        // mac(1) hasn't expired, so the still-full table refuses mac(2).
+        assert_eq!(table.lookup(&mac(1)), Some(peer(1001)));
+        assert_eq!(table.lookup(&mac(2)), None);
+    }
+
+    #[test]
+    fn test_l3_table_longest_prefix_match() {
+        let table = L3Table::new(vec![
+            ("10.0.0.0/8".parse().unwrap(), peer(1)),
+            ("10.1.0.0/16".parse().unwrap(), peer(2)),
+        ]);
+
+        assert_eq!(table.lookup("10.1.2.3".parse().unwrap()), Some(peer(2)));
+        assert_eq!(table.lookup("10.2.2.3".parse().unwrap()), Some(peer(1)));
+        assert_eq!(table.lookup("192.168.0.1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_dumb_router_builds_with_no_section() {
+        let config = RouterConfig { name: RouterChoice::Dumb, ..Default::default() };
+
+        assert!(matches!(ForwardingTable::try_from(&config).unwrap(), ForwardingTable::Dumb));
+    }
+
+    #[test]
+    fn test_switch_router_without_section_is_an_error() {
+        let config = RouterConfig { name: RouterChoice::Switch, ..Default::default() };
+
+        let err = ForwardingTable::try_from(&config).unwrap_err();
+        assert!(err.to_string().contains("router.switch"));
+    }
+
+    #[test]
+    fn test_switch_router_with_section_builds() {
+        let config = RouterConfig {
+            name: RouterChoice::Switch,
+            switch: Some(SwitchRouterConfig::default()),
+            ..Default::default()
+        };
+
+        assert!(matches!(ForwardingTable::try_from(&config).unwrap(), ForwardingTable::Switch(_)));
+    }
+
+    #[test]
+    fn test_l3_router_without_section_is_an_error() {
+        let config = RouterConfig { name: RouterChoice::Router, ..Default::default() };
+
+        let err = ForwardingTable::try_from(&config).unwrap_err();
+        assert!(err.to_string().contains("router.l3"));
+    }
+}
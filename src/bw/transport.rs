@@ -0,0 +1,475 @@
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::Arc;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use rustls::{ClientConfig, ClientSession, NoClientAuth, ServerConfig as TlsServerConfig, ServerSession, Session, StreamOwned};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::{client as ws_client, WebSocket};
+
+use super::config::{CertificateAuthorityConfig, TransportConfig, TransportType};
+
+/// A single encrypted datapath frame carried over whatever transport is
+/// configured. Every transport speaks this same interface, so the router
+/// never has to know whether it's sitting on raw UDP or a WebSocket.
+pub trait FrameTransport {
+    fn send_frame(&mut self, peer: SocketAddr, frame: &[u8]) -> Result<(), Error>;
+    fn recv_frame(&mut self) -> Result<(SocketAddr, Bytes), Error>;
+}
+
+/// Build the client side of whichever transport `config` selects and dial
+/// `peer`. This is the single dispatch point `transport.type` actually
+/// drives; everything downstream only ever sees a `FrameTransport`.
+pub fn connect(
+    config: &TransportConfig,
+    peer: SocketAddr,
+    ca: Option<&CertificateAuthorityConfig>,
+) -> Result<Box<dyn FrameTransport>, Error> {
+    match config.transport_type {
+        TransportType::Udp => {
+            let bind_addr = if peer.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse().unwrap();
+            Ok(Box::new(UdpTransport::bind(bind_addr)?))
+        }
+
+        TransportType::Tcp => {
+            let transport = TcpTransport::connect(peer)?;
+            apply_keepalive(&transport.stream, config.keepalive_interval)?;
+            Ok(Box::new(transport))
+        }
+
+        TransportType::Tls => {
+            let sni = config.sni.as_deref().ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "transport.type = \"tls\" requires transport.sni")
+            })?;
+
+            let ca = ca.ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "transport.type = \"tls\" requires a CertificateAuthorityConfig [auth]")
+            })?;
+
+            let transport = TlsTransport::connect(peer, sni, ca)?;
+            apply_keepalive(&transport.stream.sock, config.keepalive_interval)?;
+            Ok(Box::new(transport))
+        }
+
+        TransportType::WebSocket => {
+            let default_host = peer.to_string();
+            let host_header = config.host_header.as_deref().unwrap_or(&default_host);
+
+            let transport = WebSocketTransport::connect(peer, host_header)?;
+            apply_keepalive(transport.socket.get_ref(), config.keepalive_interval)?;
+            Ok(Box::new(transport))
+        }
+    }
+}
+
+/// Accept one inbound peer on `listener` according to `config`. UDP has no
+/// accept step of its own (it's connectionless - bind a `UdpTransport` and
+/// use it directly), so it's an error to call this with `transport.type =
+/// "udp"`.
+pub fn accept(
+    config: &TransportConfig,
+    listener: &TcpListener,
+    identity: Option<&TlsIdentity>,
+) -> Result<Box<dyn FrameTransport>, Error> {
+    match config.transport_type {
+        TransportType::Udp => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "transport.type = \"udp\" has no accept() step; bind a UdpTransport instead",
+        )),
+
+        TransportType::Tcp => {
+            let transport = TcpTransport::accept(listener)?;
+            apply_keepalive(&transport.stream, config.keepalive_interval)?;
+            Ok(Box::new(transport))
+        }
+
+        TransportType::Tls => {
+            let identity = identity.ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "transport.type = \"tls\" requires a server identity to accept with")
+            })?;
+
+            let transport = TlsTransport::accept(listener, identity)?;
+            apply_keepalive(&transport.stream.sock, config.keepalive_interval)?;
+            Ok(Box::new(transport))
+        }
+
+        TransportType::WebSocket => {
+            let transport = WebSocketTransport::accept(listener)?;
+            apply_keepalive(transport.socket.get_ref(), config.keepalive_interval)?;
+            Ok(Box::new(transport))
+        }
+    }
+}
+
+/// Enable TCP keepalive with `interval_secs` between probes. Best-effort:
+/// only Linux is wired up, other platforms are a no-op since the datapath
+/// already has its own liveness checks above the transport layer.
+fn apply_keepalive(stream: &TcpStream, interval_secs: u64) -> Result<(), Error> {
+    set_keepalive(stream, interval_secs)
+}
+
+#[cfg(target_os = "linux")]
+fn set_keepalive(stream: &TcpStream, interval_secs: u64) -> Result<(), Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+    let enable: libc::c_int = 1;
+    let interval: libc::c_int = interval_secs as libc::c_int;
+    let opt_len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    unsafe {
+        if libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_KEEPALIVE,
+            &enable as *const _ as *const libc::c_void,
+            opt_len,
+        ) != 0
+        {
+            return Err(Error::last_os_error());
+        }
+
+        if libc::setsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_KEEPINTVL,
+            &interval as *const _ as *const libc::c_void,
+            opt_len,
+        ) != 0
+        {
+            return Err(Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_keepalive(_stream: &TcpStream, _interval_secs: u64) -> Result<(), Error> {
+    Ok(())
+}
+
+/// The existing behaviour: frames go out as whole UDP datagrams, no framing
+/// needed since UDP already preserves message boundaries.
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    pub fn bind(addr: SocketAddr) -> Result<Self, Error> {
+        Ok(UdpTransport { socket: UdpSocket::bind(addr)? })
+    }
+}
+
+impl FrameTransport for UdpTransport {
+    fn send_frame(&mut self, peer: SocketAddr, frame: &[u8]) -> Result<(), Error> {
+        self.socket.send_to(frame, peer)?;
+        Ok(())
+    }
+
+    fn recv_frame(&mut self) -> Result<(SocketAddr, Bytes), Error> {
+        let mut buf = [0u8; 65536];
+        let (len, peer) = self.socket.recv_from(&mut buf)?;
+        Ok((peer, Bytes::from(&buf[..len])))
+    }
+}
+
+/// Upper bound on a length-prefixed frame's declared size, matching the
+/// `UdpTransport` receive buffer. The length prefix comes straight off the
+/// wire from a possibly-hostile peer, so it must be sanity-checked before
+/// it's trusted as an allocation size.
+const MAX_FRAME_SIZE: u32 = 65536;
+
+/// Read a length prefix and reject it before it's used to size an
+/// allocation, so a peer can't OOM/abort us with a bogus multi-gigabyte
+/// length.
+fn read_frame_len<R: Read>(stream: &mut R) -> Result<usize, Error> {
+    let len = stream.read_u32::<BigEndian>()?;
+
+    if len > MAX_FRAME_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("frame length {} exceeds maximum of {} bytes", len, MAX_FRAME_SIZE),
+        ));
+    }
+
+    Ok(len as usize)
+}
+
+/// TCP has no built-in message boundaries, so frames are length-prefixed: a
+/// big-endian `u32` length followed by that many bytes.
+pub struct TcpTransport {
+    peer: SocketAddr,
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn connect(peer: SocketAddr) -> Result<Self, Error> {
+        Ok(TcpTransport { peer, stream: TcpStream::connect(peer)? })
+    }
+
+    pub fn from_stream(peer: SocketAddr, stream: TcpStream) -> Self {
+        TcpTransport { peer, stream }
+    }
+
+    pub fn listen(addr: SocketAddr) -> Result<TcpListener, Error> {
+        TcpListener::bind(addr)
+    }
+
+    pub fn accept(listener: &TcpListener) -> Result<Self, Error> {
+        let (stream, peer) = listener.accept()?;
+        Ok(TcpTransport { peer, stream })
+    }
+}
+
+impl FrameTransport for TcpTransport {
+    fn send_frame(&mut self, peer: SocketAddr, frame: &[u8]) -> Result<(), Error> {
+        if peer != self.peer {
+            return Err(Error::new(ErrorKind::AddrNotAvailable, "peer not connected over this TCP transport"));
+        }
+
+        self.stream.write_u32::<BigEndian>(frame.len() as u32)?;
+        self.stream.write_all(frame)
+    }
+
+    fn recv_frame(&mut self) -> Result<(SocketAddr, Bytes), Error> {
+        let len = read_frame_len(&mut self.stream)?;
+        let mut buf = vec![0u8; len];
+        self.stream.read_exact(&mut buf)?;
+        Ok((self.peer, Bytes::from(buf)))
+    }
+}
+
+/// The certificate chain and private key a node presents when accepting
+/// inbound TLS connections.
+pub struct TlsIdentity {
+    pub cert_chain: Vec<rustls::Certificate>,
+    pub private_key: rustls::PrivateKey,
+}
+
+/// TLS-wrapped TCP, using the CA material already configured for peer
+/// authentication (`[auth]` when it's a `CertificateAuthorityConfig`) to
+/// verify the far end, plus the transport's own `sni` option.
+/// Generic over the rustls session type so the same framing code serves
+/// both the dialing (`ClientSession`) and accepting (`ServerSession`) side.
+pub struct TlsTransport<S: Session> {
+    peer: SocketAddr,
+    stream: StreamOwned<S, TcpStream>,
+}
+
+impl TlsTransport<ClientSession> {
+    pub fn connect(peer: SocketAddr, sni: &str, ca: &CertificateAuthorityConfig) -> Result<Self, Error> {
+        let mut tls_config = ClientConfig::new();
+        let ca_pem = ca.ca.get_value()?;
+        let mut ca_reader = std::io::Cursor::new(ca_pem.as_ref());
+        tls_config
+            .root_store
+            .add_pem_file(&mut ca_reader)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "invalid CA certificate"))?;
+
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str(sni)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("invalid SNI host '{}'", sni)))?;
+
+        let session = ClientSession::new(&Arc::new(tls_config), dns_name);
+        let tcp = TcpStream::connect(peer)?;
+
+        Ok(TlsTransport {
+            peer,
+            stream: StreamOwned::new(session, tcp),
+        })
+    }
+}
+
+impl TlsTransport<ServerSession> {
+    pub fn listen(addr: SocketAddr) -> Result<TcpListener, Error> {
+        TcpListener::bind(addr)
+    }
+
+    pub fn accept(listener: &TcpListener, identity: &TlsIdentity) -> Result<Self, Error> {
+        let (tcp, peer) = listener.accept()?;
+
+        let mut tls_config = TlsServerConfig::new(NoClientAuth::new());
+        tls_config
+            .set_single_cert(identity.cert_chain.clone(), identity.private_key.clone())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid TLS server identity: {}", e)))?;
+
+        let session = ServerSession::new(&Arc::new(tls_config));
+
+        Ok(TlsTransport {
+            peer,
+            stream: StreamOwned::new(session, tcp),
+        })
+    }
+}
+
+impl<S: Session> FrameTransport for TlsTransport<S> {
+    fn send_frame(&mut self, peer: SocketAddr, frame: &[u8]) -> Result<(), Error> {
+        if peer != self.peer {
+            return Err(Error::new(ErrorKind::AddrNotAvailable, "peer not connected over this TLS transport"));
+        }
+
+        self.stream.write_u32::<BigEndian>(frame.len() as u32)?;
+        self.stream.write_all(frame)
+    }
+
+    fn recv_frame(&mut self) -> Result<(SocketAddr, Bytes), Error> {
+        let len = read_frame_len(&mut self.stream)?;
+        let mut buf = vec![0u8; len];
+        self.stream.read_exact(&mut buf)?;
+        Ok((self.peer, Bytes::from(buf)))
+    }
+}
+
+/// WebSocket upgrade over plain HTTP (`ws://`), dialed straight to the peer,
+/// so the traffic looks like HTTP on the wire. Each frame is sent as one
+/// binary WS message. TLS-wrapped WebSocket (`wss://`) and connecting
+/// through an HTTP proxy are not supported.
+pub struct WebSocketTransport {
+    peer: SocketAddr,
+    socket: WebSocket<TcpStream>,
+}
+
+impl WebSocketTransport {
+    pub fn connect(peer: SocketAddr, host_header: &str) -> Result<Self, Error> {
+        let tcp = TcpStream::connect(peer)?;
+        let url = format!("ws://{}/", host_header);
+        let request = url
+            .into_client_request()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+
+        let (socket, _response) = ws_client(request, tcp)
+            .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?;
+
+        Ok(WebSocketTransport { peer, socket })
+    }
+
+    pub fn listen(addr: SocketAddr) -> Result<TcpListener, Error> {
+        TcpListener::bind(addr)
+    }
+
+    pub fn accept(listener: &TcpListener) -> Result<Self, Error> {
+        let (tcp, peer) = listener.accept()?;
+
+        let socket = tungstenite::accept(tcp).map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?;
+
+        Ok(WebSocketTransport { peer, socket })
+    }
+}
+
+impl FrameTransport for WebSocketTransport {
+    fn send_frame(&mut self, peer: SocketAddr, frame: &[u8]) -> Result<(), Error> {
+        if peer != self.peer {
+            return Err(Error::new(ErrorKind::AddrNotAvailable, "peer not connected over this WebSocket transport"));
+        }
+
+        self.socket
+            .write_message(tungstenite::Message::Binary(frame.to_vec()))
+            .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))
+    }
+
+    fn recv_frame(&mut self) -> Result<(SocketAddr, Bytes), Error> {
+        let message = self
+            .socket
+            .read_message()
+            .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?;
+
+        match message {
+            tungstenite::Message::Binary(data) => Ok((self.peer, Bytes::from(data))),
+            _ => Err(Error::new(ErrorKind::InvalidData, "expected a binary WebSocket frame")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::{connect, FrameTransport, TcpTransport, TransportConfig, TransportType, UdpTransport};
+
+    fn loopback(port: u16) -> std::net::SocketAddr {
+        format!("127.0.0.1:{}", port).parse().unwrap()
+    }
+
+    #[test]
+    fn test_udp_round_trip() {
+        let mut server = UdpTransport::bind(loopback(0)).unwrap();
+        let server_addr = server.socket.local_addr().unwrap();
+
+        let config = TransportConfig { transport_type: TransportType::Udp, ..Default::default() };
+        let mut client = connect(&config, server_addr, None).unwrap();
+
+        client.send_frame(server_addr, b"hello").unwrap();
+        let (_, frame) = server.recv_frame().unwrap();
+        assert_eq!(&frame[..], b"hello");
+    }
+
+    #[test]
+    fn test_tcp_round_trip_through_dispatch() {
+        let listener = TcpListener::bind(loopback(0)).unwrap();
+        let server_addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut server = TcpTransport::accept(&listener).unwrap();
+            let (peer, frame) = server.recv_frame().unwrap();
+            server.send_frame(peer, &frame).unwrap();
+        });
+
+        let config = TransportConfig { transport_type: TransportType::Tcp, ..Default::default() };
+        let mut client = connect(&config, server_addr, None).unwrap();
+
+        client.send_frame(server_addr, b"ping-pong").unwrap();
+        let (_, echoed) = client.recv_frame().unwrap();
+        assert_eq!(&echoed[..], b"ping-pong");
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_tls_requires_sni() {
+        let config = TransportConfig { transport_type: TransportType::Tls, ..Default::default() };
+        let err = connect(&config, loopback(1), None).err().unwrap();
+        assert!(err.to_string().contains("sni"));
+    }
+
+    #[test]
+    fn test_tls_requires_ca() {
+        let config = TransportConfig {
+            transport_type: TransportType::Tls,
+            sni: Some("example.com".to_string()),
+            ..Default::default()
+        };
+        let err = connect(&config, loopback(1), None).err().unwrap();
+        assert!(err.to_string().contains("CertificateAuthorityConfig"));
+    }
+
+    #[test]
+    fn test_accept_rejects_udp() {
+        let listener = TcpListener::bind(loopback(0)).unwrap();
+        let config = TransportConfig { transport_type: TransportType::Udp, ..Default::default() };
+        let err = super::accept(&config, &listener, None).err().unwrap();
+        assert!(err.to_string().contains("udp"));
+    }
+
+    #[test]
+    fn test_tcp_rejects_oversized_frame_length() {
+        use std::io::Write as _;
+
+        use byteorder::{BigEndian, WriteBytesExt};
+
+        let listener = TcpListener::bind(loopback(0)).unwrap();
+        let server_addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut server = TcpTransport::accept(&listener).unwrap();
+            server.recv_frame()
+        });
+
+        let mut client = std::net::TcpStream::connect(server_addr).unwrap();
+        client.write_u32::<BigEndian>(super::MAX_FRAME_SIZE + 1).unwrap();
+
+        let err = handle.join().unwrap().unwrap_err();
+        assert!(err.to_string().contains("exceeds maximum"));
+    }
+}